@@ -1,34 +1,164 @@
 #![allow(dead_code)]
 
+use hashlink::LinkedHashMap;
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
-struct Cache<V> {
-    storage: HashMap<String, (V, Instant)>,
+/// Controls when a [`Cache`] entry's expiration deadline advances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpirationMode {
+    /// An entry expires `ttl` after it was inserted or last refreshed,
+    /// regardless of how often it's read.
+    Fixed,
+    /// A successful `get` resets the entry's expiration to
+    /// `Instant::now() + ttl`, so a hot key never expires.
+    Sliding,
 }
 
-impl<V: Clone> Cache<V> {
+/// Why an entry was removed from a [`Cache`], passed to an eviction
+/// handler registered via [`Cache::with_eviction_handler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvictionReason {
+    /// The entry's TTL had elapsed, discovered lazily (`get`) or via
+    /// `expire_all`.
+    Expired,
+    /// The entry was the least-recently-used one and was dropped to
+    /// stay within the cache's `capacity`.
+    Capacity,
+    /// The entry was removed by an explicit call to `expire`.
+    Explicit,
+    /// The entry was removed as part of a `clear`.
+    Cleared,
+}
+
+/// Callback invoked for each entry a [`Cache`] removes; see
+/// [`Cache::with_eviction_handler`].
+type EvictionHandler<K, V> = Box<dyn FnMut(&K, V, EvictionReason)>;
+
+/// An entry cache with per-key TTL expiration and, optionally, a bounded
+/// capacity evicted on an LRU basis.
+///
+/// Entries are stored in a [`LinkedHashMap`], which tracks insertion/access
+/// order so the least-recently-used entry can be evicted in O(1) once the
+/// cache grows past its `capacity`.
+struct Cache<K, V> {
+    storage: LinkedHashMap<K, (V, Instant, Duration)>,
+    capacity: Option<usize>,
+    mode: ExpirationMode,
+    on_evict: Option<EvictionHandler<K, V>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
     fn new() -> Self {
+        Self::with_capacity(None)
+    }
+
+    /// Construct a cache with an optional maximum entry count. When
+    /// `capacity` is `Some(n)`, inserting past `n` entries evicts the
+    /// least-recently-used entry, in addition to normal TTL expiration.
+    fn with_capacity(capacity: Option<usize>) -> Self {
+        Self::with_options(capacity, ExpirationMode::Fixed)
+    }
+
+    /// Construct a cache with sliding-TTL expiration: a successful `get`
+    /// extends the entry's lifetime by its original `ttl` instead of
+    /// leaving a fixed deadline from insertion.
+    fn with_sliding_expiration(capacity: Option<usize>) -> Self {
+        Self::with_options(capacity, ExpirationMode::Sliding)
+    }
+
+    /// Construct a cache that invokes `handler` for every entry it
+    /// removes, whether via `expire`, `expire_all`, capacity-driven LRU
+    /// eviction, lazy expiry during `get`, or `clear`.
+    fn with_eviction_handler(handler: EvictionHandler<K, V>) -> Self {
+        Self::with_options_and_handler(None, ExpirationMode::Fixed, Some(handler))
+    }
+
+    /// Construct a cache with both a capacity/mode and an eviction
+    /// handler, e.g. to observe `EvictionReason::Capacity` evictions from
+    /// an LRU-bounded cache.
+    fn with_capacity_and_eviction_handler(
+        capacity: Option<usize>,
+        mode: ExpirationMode,
+        handler: EvictionHandler<K, V>,
+    ) -> Self {
+        Self::with_options_and_handler(capacity, mode, Some(handler))
+    }
+
+    fn with_options(capacity: Option<usize>, mode: ExpirationMode) -> Self {
+        Self::with_options_and_handler(capacity, mode, None)
+    }
+
+    fn with_options_and_handler(
+        capacity: Option<usize>,
+        mode: ExpirationMode,
+        on_evict: Option<EvictionHandler<K, V>>,
+    ) -> Self {
         Self {
-            storage: HashMap::new(),
+            storage: LinkedHashMap::new(),
+            capacity,
+            mode,
+            on_evict,
         }
     }
 
-    fn set(&mut self, key: String, value: V, ttl: Duration) {
-        self.storage.insert(key, (value, Instant::now() + ttl));
+    /// Remove `key` from `storage`, if present, firing `on_evict` with
+    /// `reason`. Every eviction site routes through here so the callback
+    /// fires consistently.
+    fn remove<Q>(&mut self, key: &Q, reason: EvictionReason) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (k, (value, _, _)) = self.storage.remove_entry(key)?;
+        if let Some(handler) = &mut self.on_evict {
+            handler(&k, value.clone(), reason);
+        }
+        Some(value)
     }
 
-    fn get(&self, key: &str) -> Option<V> {
-        self.storage.get(key).and_then(|(value, expiration)| {
-            if Instant::now() < *expiration {
-                Some(value.clone())
-            } else {
-                None
+    fn set(&mut self, key: K, value: V, ttl: Duration) {
+        self.storage.to_back(&key);
+        self.storage.insert(key, (value, Instant::now() + ttl, ttl));
+        if let Some(capacity) = self.capacity {
+            while self.storage.len() > capacity {
+                let Some(oldest) = self.storage.front().map(|(k, _)| k.clone()) else {
+                    break;
+                };
+                self.remove(&oldest, EvictionReason::Capacity);
             }
-        })
+        }
+    }
+
+    fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let expired = match self.storage.get(key) {
+            Some((_, expiration, _)) => Instant::now() >= *expiration,
+            None => return None,
+        };
+        if expired {
+            self.remove(key, EvictionReason::Expired);
+            return None;
+        }
+        if self.mode == ExpirationMode::Sliding
+            && let Some((_, expiration, ttl)) = self.storage.get_mut(key)
+        {
+            *expiration = Instant::now() + *ttl;
+        }
+        self.storage.to_back(key);
+        self.storage.get(key).map(|(value, _, _)| value.clone())
     }
 
-    fn get_or_set(&mut self, key: String, value: V, ttl: Duration) -> V {
+    fn get_or_set(&mut self, key: K, value: V, ttl: Duration) -> V {
         if let Some(existing_value) = self.get(&key) {
             existing_value.clone()
         } else {
@@ -37,9 +167,44 @@ impl<V: Clone> Cache<V> {
         }
     }
 
-    fn expire(&mut self, key: &str) -> Option<V> {
-        let (value, ttl) = self.storage.remove(key)?;
-        if Instant::now() < ttl {
+    /// Like [`Self::get_or_set`], but only computes `value` on a miss,
+    /// rather than building it eagerly on every call.
+    fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, ttl: Duration, f: F) -> V {
+        if let Some(existing_value) = self.get(&key) {
+            existing_value
+        } else {
+            let value = f();
+            self.set(key, value.clone(), ttl);
+            value
+        }
+    }
+
+    /// Fallible variant of [`Self::get_or_insert_with`]. Nothing is
+    /// inserted, and the error is propagated to the caller, if `f` fails.
+    fn get_or_try_insert_with<F, E>(&mut self, key: K, ttl: Duration, f: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        if let Some(existing_value) = self.get(&key) {
+            Ok(existing_value)
+        } else {
+            let value = f()?;
+            self.set(key, value.clone(), ttl);
+            Ok(value)
+        }
+    }
+
+    fn expire<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let expiration = match self.storage.get(key) {
+            Some((_, expiration, _)) => *expiration,
+            None => return None,
+        };
+        let value = self.remove(key, EvictionReason::Explicit)?;
+        if Instant::now() < expiration {
             Some(value)
         } else {
             None
@@ -47,13 +212,25 @@ impl<V: Clone> Cache<V> {
     }
 
     fn expire_all(&mut self) {
-        self.storage.retain(|_, (_, ttl)| Instant::now() < *ttl);
+        let expired: Vec<K> = self
+            .storage
+            .iter()
+            .filter(|(_, (_, expiration, _))| Instant::now() >= *expiration)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.remove(&key, EvictionReason::Expired);
+        }
     }
 
-    fn refresh(&mut self, key: &str, ttl: Duration) -> bool {
-        if let Some((value, _)) = self.storage.get(key) {
+    fn refresh<Q>(&mut self, key: &Q, ttl: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if let Some((value, _, _)) = self.storage.get(key) {
             let value = value.clone();
-            self.set(key.to_string(), value, ttl);
+            self.set(key.to_owned(), value, ttl);
             true
         } else {
             false
@@ -61,7 +238,205 @@ impl<V: Clone> Cache<V> {
     }
 
     fn clear(&mut self) {
-        self.storage.clear();
+        if self.on_evict.is_some() {
+            let keys: Vec<K> = self.storage.iter().map(|(key, _)| key.clone()).collect();
+            for key in keys {
+                self.remove(&key, EvictionReason::Cleared);
+            }
+        } else {
+            self.storage.clear();
+        }
+    }
+}
+
+/// The result of a non-blocking lookup via [`ConcurrentCache::try_get`].
+#[derive(Debug, PartialEq, Eq)]
+enum TryGetResult<V> {
+    /// The key was present and unexpired.
+    Hit(V),
+    /// The key was absent or expired.
+    Miss,
+    /// The key's shard lock is currently held by another thread.
+    Contended,
+}
+
+/// Point-in-time snapshot of a [`ConcurrentCache`]'s hit-rate counters,
+/// returned by [`ConcurrentCache::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct CacheStats {
+    hits: u64,
+    misses: u64,
+    insertions: u64,
+    expirations: u64,
+}
+
+/// Atomic backing counters for [`CacheStats`], cheap to update from any
+/// shard without taking a lock.
+#[derive(Default)]
+struct CacheStatsCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+impl CacheStatsCounters {
+    fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A thread-safe, `&self`-mutable cache with per-key TTL expiration.
+///
+/// Keys are partitioned across a fixed number of shards, each guarded by
+/// its own [`RwLock`], so unrelated keys on different shards don't
+/// contend with one another the way a single `Mutex<HashMap<_>>` would.
+/// Share across threads via `Arc<ConcurrentCache<K, V>>`.
+struct ConcurrentCache<K, V> {
+    shards: Vec<RwLock<HashMap<K, (V, Instant)>>>,
+    shard_mask: usize,
+    stats: CacheStatsCounters,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> ConcurrentCache<K, V> {
+    fn new() -> Self {
+        let shard_count = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .next_power_of_two();
+        Self {
+            shards: (0..shard_count).map(|_| RwLock::new(HashMap::new())).collect(),
+            shard_mask: shard_count - 1,
+            stats: CacheStatsCounters::default(),
+        }
+    }
+
+    fn shard_index<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) & self.shard_mask
+    }
+
+    fn set(&self, key: K, value: V, ttl: Duration) {
+        let shard = &self.shards[self.shard_index(&key)];
+        shard
+            .write()
+            .unwrap()
+            .insert(key, (value, Instant::now() + ttl));
+        self.stats.insertions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        let value = shard.read().unwrap().get(key).and_then(|(value, expiration)| {
+            if Instant::now() < *expiration {
+                Some(value.clone())
+            } else {
+                None
+            }
+        });
+        if value.is_some() {
+            self.stats.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        value
+    }
+
+    /// Like [`Self::get`], but returns [`TryGetResult::Contended`] instead
+    /// of blocking when the key's shard lock is currently held by another
+    /// thread.
+    fn try_get<Q>(&self, key: &Q) -> TryGetResult<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        let guard = match shard.try_read() {
+            Ok(guard) => guard,
+            Err(_) => return TryGetResult::Contended,
+        };
+        match guard.get(key) {
+            Some((value, expiration)) if Instant::now() < *expiration => {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                TryGetResult::Hit(value.clone())
+            }
+            _ => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                TryGetResult::Miss
+            }
+        }
+    }
+
+    fn expire<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        let (value, ttl) = shard.write().unwrap().remove(key)?;
+        if Instant::now() < ttl {
+            Some(value)
+        } else {
+            self.stats.expirations.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    fn expire_all(&self) {
+        for shard in &self.shards {
+            let mut shard = shard.write().unwrap();
+            let before = shard.len();
+            shard.retain(|_, (_, ttl)| Instant::now() < *ttl);
+            let expired = before - shard.len();
+            self.stats
+                .expirations
+                .fetch_add(expired as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+    }
+
+    /// A snapshot of this cache's hit/miss/insertion/expiration counters,
+    /// useful for tuning TTLs and sizing the `sweep_interval`.
+    fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Spawn a background thread that periodically reaps expired entries
+    /// via [`Self::expire_all`], so write-once-never-read keys don't leak
+    /// memory indefinitely. The thread runs for as long as `self` (an
+    /// `Arc<ConcurrentCache<K, V>>`) has outstanding clones.
+    fn spawn_sweeper(self: &Arc<Self>, sweep_interval: Duration) -> JoinHandle<()>
+    where
+        K: Send + Sync + 'static,
+        V: Send + Sync + 'static,
+    {
+        let cache = Arc::downgrade(self);
+        thread::spawn(move || loop {
+            thread::sleep(sweep_interval);
+            match cache.upgrade() {
+                Some(cache) => cache.expire_all(),
+                None => return,
+            }
+        })
     }
 }
 
@@ -133,9 +508,30 @@ mod tests {
         let ttl = Duration::from_secs(10);
         cache.set(key.clone(), "value".to_owned(), ttl);
         std::thread::sleep(Duration::from_secs(2));
-        assert_eq!(cache.refresh(&key, Duration::from_secs(10)), true);
+        assert!(cache.refresh(&key, Duration::from_secs(10)));
         cache.expire(&key);
-        assert_eq!(cache.refresh("key", Duration::from_secs(10)), false);
+        assert!(!cache.refresh("key", Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_capacity_evicts_least_recently_used() {
+        let mut cache = Cache::with_capacity(Some(2));
+        let ttl = Duration::from_secs(10);
+        cache.set("key1".to_owned(), "value1".to_owned(), ttl);
+        cache.set("key2".to_owned(), "value2".to_owned(), ttl);
+        // Touch key1 so key2 becomes the least-recently-used entry.
+        assert_eq!(cache.get("key1"), Some("value1".to_string()));
+        cache.set("key3".to_owned(), "value3".to_owned(), ttl);
+        assert_eq!(cache.get("key2"), None);
+        assert_eq!(cache.get("key1"), Some("value1".to_string()));
+        assert_eq!(cache.get("key3"), Some("value3".to_string()));
+    }
+
+    #[test]
+    fn test_integer_keys() {
+        let mut cache: Cache<u64, String> = Cache::new();
+        cache.set(1, "value".to_owned(), Duration::from_secs(10));
+        assert_eq!(cache.get(&1), Some("value".to_string()));
     }
 
     #[test]
@@ -155,4 +551,156 @@ mod tests {
         assert_eq!(cache.get("key"), None);
         assert_eq!(cache.get("key2"), None);
     }
+
+    #[test]
+    fn test_get_or_insert_with_computes_once() {
+        let mut cache = Cache::new();
+        let mut calls = 0;
+        let value = cache.get_or_insert_with("key".to_owned(), Duration::from_secs(10), || {
+            calls += 1;
+            "value".to_owned()
+        });
+        assert_eq!(value, "value".to_string());
+        let value = cache.get_or_insert_with("key".to_owned(), Duration::from_secs(10), || {
+            calls += 1;
+            "other".to_owned()
+        });
+        assert_eq!(value, "value".to_string());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with_propagates_err() {
+        let mut cache: Cache<String, String> = Cache::new();
+        let result = cache.get_or_try_insert_with("key".to_owned(), Duration::from_secs(10), || {
+            Err::<String, _>("failed")
+        });
+        assert_eq!(result, Err("failed"));
+        assert_eq!(cache.get("key"), None);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with_caches_ok() {
+        let mut cache = Cache::new();
+        let result = cache.get_or_try_insert_with("key".to_owned(), Duration::from_secs(10), || {
+            Ok::<_, &str>("value".to_owned())
+        });
+        assert_eq!(result, Ok("value".to_string()));
+        assert_eq!(cache.get("key"), Some("value".to_string()));
+    }
+
+    #[test]
+    fn test_eviction_handler_fires_on_capacity_and_explicit_removal() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let evictions = Rc::new(RefCell::new(Vec::new()));
+        let recorder = Rc::clone(&evictions);
+        let mut cache: Cache<String, String> = Cache::with_capacity_and_eviction_handler(
+            Some(1),
+            ExpirationMode::Fixed,
+            Box::new(move |key: &String, value, reason| {
+                recorder.borrow_mut().push((key.clone(), value, reason));
+            }),
+        );
+        let ttl = Duration::from_secs(10);
+        cache.set("key1".to_owned(), "value1".to_owned(), ttl);
+        cache.set("key2".to_owned(), "value2".to_owned(), ttl);
+        cache.expire("key2");
+        assert_eq!(
+            *RefCell::borrow(&evictions),
+            vec![
+                (
+                    "key1".to_owned(),
+                    "value1".to_owned(),
+                    EvictionReason::Capacity
+                ),
+                (
+                    "key2".to_owned(),
+                    "value2".to_owned(),
+                    EvictionReason::Explicit
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sliding_expiration_extends_on_access() {
+        let mut cache = Cache::with_sliding_expiration(None);
+        let ttl = Duration::from_secs(2);
+        cache.set("key".to_owned(), "value".to_owned(), ttl);
+        // Keep touching the key faster than it would expire under a fixed
+        // deadline; each `get` should push the deadline back out.
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_secs(1));
+            assert_eq!(cache.get("key"), Some("value".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_get_set() {
+        let cache = ConcurrentCache::new();
+        cache.set("key".to_owned(), "value".to_owned(), Duration::from_secs(10));
+        assert_eq!(cache.get("key"), Some("value".to_string()));
+    }
+
+    #[test]
+    fn test_concurrent_expire() {
+        let cache = ConcurrentCache::new();
+        let ttl = Duration::from_secs(1);
+        cache.set("key".to_owned(), "value".to_owned(), ttl);
+        std::thread::sleep(ttl + Duration::from_secs(1));
+        assert_eq!(cache.get("key"), None);
+    }
+
+    #[test]
+    fn test_concurrent_shared_across_threads() {
+        use std::sync::Arc;
+
+        let cache = Arc::new(ConcurrentCache::new());
+        let mut handles = Vec::new();
+        for i in 0..8 {
+            let cache = Arc::clone(&cache);
+            handles.push(std::thread::spawn(move || {
+                cache.set(format!("key{i}"), i, Duration::from_secs(10));
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        for i in 0..8 {
+            assert_eq!(cache.get(&format!("key{i}")), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_try_get_miss() {
+        let cache: ConcurrentCache<String, String> = ConcurrentCache::new();
+        assert_eq!(cache.try_get("key"), TryGetResult::Miss);
+    }
+
+    #[test]
+    fn test_concurrent_stats_tracks_hits_and_misses() {
+        let cache = ConcurrentCache::new();
+        cache.set("key".to_owned(), "value".to_owned(), Duration::from_secs(10));
+        cache.get("key");
+        cache.get("missing");
+        let stats = cache.stats();
+        assert_eq!(stats.insertions, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+    }
+
+    #[test]
+    fn test_concurrent_sweeper_reaps_expired_entries() {
+        let cache = Arc::new(ConcurrentCache::new());
+        let ttl = Duration::from_secs(1);
+        cache.set("key".to_owned(), "value".to_owned(), ttl);
+        let sweeper = cache.spawn_sweeper(Duration::from_millis(200));
+        std::thread::sleep(ttl + Duration::from_secs(1));
+        assert_eq!(cache.get("key"), None);
+        assert!(cache.stats().expirations >= 1);
+        drop(cache);
+        sweeper.join().unwrap();
+    }
 }